@@ -1,5 +1,11 @@
+use std::env;
+use std::fmt;
+use std::os::unix::net::UnixDatagram;
+
 use log::*;
 
+const JOURNALD_SOCKET_PATH: &str = "/run/systemd/journal/socket";
+
 // Public interfaces.
 pub enum LoggerModuleFilterKey {
     Module(&'static str, LevelFilter),
@@ -9,13 +15,144 @@ pub enum LoggerModuleFilterKey {
 pub fn init(
     module_max_levels: impl Into<Vec<LoggerModuleFilterKey>>,
 ) -> Result<(), SetLoggerError> {
-    let module_max_levels = module_max_levels.into();
+    build_logger(module_max_levels.into(), None, Box::new(StdioSink))
+}
 
-    set_max_level(most_verbose_level(&module_max_levels));
-    set_boxed_logger(Box::new(AppLogger { module_max_levels }))
+/// A sink for fully formatted log lines, used once filtering and formatting
+/// has already happened. See [`StdioSink`] for the built-in policy.
+pub trait LogSink: Send + Sync {
+    fn write_line(&self, level: Level, line: &str);
+}
+
+/// Routes `Error`/`Warn` lines to stderr and everything else to stdout. This
+/// is the sink used by [`init`] and [`init_journald`].
+pub struct StdioSink;
+
+impl LogSink for StdioSink {
+    fn write_line(&self, level: Level, line: &str) {
+        match level {
+            Level::Error | Level::Warn => eprintln!("{}", line),
+            Level::Info | Level::Debug | Level::Trace => println!("{}", line),
+        }
+    }
+}
+
+/// Like [`init`], but writes through a custom [`LogSink`] instead of the
+/// default stdout/stderr routing.
+pub fn init_with_sink(
+    module_max_levels: impl Into<Vec<LoggerModuleFilterKey>>,
+    sink: impl LogSink + 'static,
+) -> Result<(), SetLoggerError> {
+    build_logger(module_max_levels.into(), None, Box::new(sink))
+}
+
+/// Error from [`init_from_str`]/[`init_from_env`].
+#[derive(Debug)]
+pub enum InitFromStrError {
+    Parse(String),
+    SetLogger(SetLoggerError),
+}
+
+impl fmt::Display for InitFromStrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitFromStrError::Parse(message) => write!(f, "{}", message),
+            InitFromStrError::SetLogger(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for InitFromStrError {}
+
+impl From<SetLoggerError> for InitFromStrError {
+    fn from(error: SetLoggerError) -> Self {
+        InitFromStrError::SetLogger(error)
+    }
+}
+
+/// Initializes the logger from filter directives in the given environment
+/// variable (e.g. `RUST_LOG`), mirroring the `RUST_LOG=info ./main`
+/// convention used across the `log` ecosystem.
+pub fn init_from_env(env_name: &str) -> Result<(), InitFromStrError> {
+    let directives = env::var(env_name).unwrap_or_default();
+    init_from_str(&directives)
+}
+
+/// Initializes the logger from a `RUST_LOG`-style directive string
+/// (comma-separated `target=level` entries plus an optional bare `level`
+/// for the default).
+pub fn init_from_str(directives: &str) -> Result<(), InitFromStrError> {
+    let module_max_levels = parse_directives(directives)?;
+    init(module_max_levels)?;
+    Ok(())
+}
+
+fn parse_directives(directives: &str) -> Result<Vec<LoggerModuleFilterKey>, InitFromStrError> {
+    directives
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(parse_directive)
+        .collect()
+}
+
+fn parse_directive(directive: &str) -> Result<LoggerModuleFilterKey, InitFromStrError> {
+    match directive.split_once('=') {
+        Some((target, level)) => {
+            let level = parse_level_filter(level)?;
+            // The public filter key borrows a `&'static str`, so the
+            // environment-provided target is leaked once at startup.
+            let target: &'static str = Box::leak(target.to_string().into_boxed_str());
+            Ok(LoggerModuleFilterKey::Module(target, level))
+        }
+        None => Ok(LoggerModuleFilterKey::Default(parse_level_filter(
+            directive,
+        )?)),
+    }
+}
+
+fn parse_level_filter(level: &str) -> Result<LevelFilter, InitFromStrError> {
+    match level.to_ascii_lowercase().as_str() {
+        "off" => Ok(LevelFilter::Off),
+        "error" => Ok(LevelFilter::Error),
+        "warn" => Ok(LevelFilter::Warn),
+        "info" => Ok(LevelFilter::Info),
+        "debug" => Ok(LevelFilter::Debug),
+        "trace" => Ok(LevelFilter::Trace),
+        _ => Err(InitFromStrError::Parse(format!(
+            "unknown log level filter: `{}`",
+            level
+        ))),
+    }
+}
+
+/// Initializes the logger to write directly to the systemd journal, falling
+/// back to the same stdout/stderr [`StdioSink`] as [`init`] if the journal
+/// socket is unavailable.
+pub fn init_journald(
+    module_max_levels: impl Into<Vec<LoggerModuleFilterKey>>,
+) -> Result<(), SetLoggerError> {
+    let journald = UnixDatagram::unbound()
+        .and_then(|socket| socket.connect(JOURNALD_SOCKET_PATH).map(|_| socket))
+        .ok();
+
+    build_logger(module_max_levels.into(), journald, Box::new(StdioSink))
 }
 
 // Internals.
+fn build_logger(
+    module_max_levels: Vec<LoggerModuleFilterKey>,
+    journald: Option<UnixDatagram>,
+    sink: Box<dyn LogSink>,
+) -> Result<(), SetLoggerError> {
+    set_max_level(most_verbose_level(&module_max_levels));
+    set_boxed_logger(Box::new(AppLogger {
+        module_max_levels,
+        journald,
+        sink,
+    }))
+}
+
 #[inline]
 fn most_verbose_level(module_max_levels: &[LoggerModuleFilterKey]) -> LevelFilter {
     let mut most_verbose_level = LevelFilter::Off;
@@ -32,6 +169,15 @@ fn most_verbose_level(module_max_levels: &[LoggerModuleFilterKey]) -> LevelFilte
     most_verbose_level
 }
 
+/// Whether `target` equals `name` or is a `::`-delimited child of it.
+#[inline]
+fn module_matches(target: &str, name: &str) -> bool {
+    target == name
+        || target
+            .strip_prefix(name)
+            .is_some_and(|rest| rest.starts_with("::"))
+}
+
 #[inline]
 fn level_to_severity_rfc5424(level: Level) -> usize {
     match level {
@@ -45,15 +191,25 @@ fn level_to_severity_rfc5424(level: Level) -> usize {
 
 struct AppLogger {
     module_max_levels: Vec<LoggerModuleFilterKey>,
+    journald: Option<UnixDatagram>,
+    sink: Box<dyn LogSink>,
 }
 impl Log for AppLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
         let mut default_level: Option<LevelFilter> = None;
+        let mut most_specific_match: Option<(&str, LevelFilter)> = None;
+
         for level in &self.module_max_levels {
             match level {
                 LoggerModuleFilterKey::Module(name, level) => {
-                    if metadata.target().starts_with(name) {
-                        return metadata.level().to_level_filter() <= *level;
+                    if module_matches(metadata.target(), name) {
+                        let is_more_specific = match most_specific_match {
+                            Some((matched_name, _)) => name.len() > matched_name.len(),
+                            None => true,
+                        };
+                        if is_more_specific {
+                            most_specific_match = Some((name, *level));
+                        }
                     }
                 }
                 LoggerModuleFilterKey::Default(level) => {
@@ -64,37 +220,176 @@ impl Log for AppLogger {
             }
         }
 
-        // Test with default level
-        if let Some(default_level) = default_level {
-            metadata.level().to_level_filter() <= default_level
-        } else {
-            false
+        // Prefer the most specific module match, falling back to the default level
+        match most_specific_match.map(|(_, level)| level).or(default_level) {
+            Some(level) => metadata.level().to_level_filter() <= level,
+            None => false,
         }
     }
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            println!(
-                "<{}>{}: {}",
-                level_to_severity_rfc5424(record.level()),
-                record.target(),
-                record.args()
-            );
+            if let Some(socket) = &self.journald {
+                if socket.send(&journald_entry(record)).is_ok() {
+                    return;
+                }
+            }
+
+            let key_values = format_key_values(record);
+            let line = if key_values.is_empty() {
+                format!(
+                    "<{}>{}: {}",
+                    level_to_severity_rfc5424(record.level()),
+                    record.target(),
+                    record.args()
+                )
+            } else {
+                format!(
+                    "<{}>{}: {} [{}]",
+                    level_to_severity_rfc5424(record.level()),
+                    record.target(),
+                    record.args(),
+                    key_values
+                )
+            };
+
+            self.sink.write_line(record.level(), &line);
         }
     }
 
     fn flush(&self) {}
 }
 
+/// Formats a record's key-values as a space-separated `key=value` list.
+#[cfg(feature = "kv")]
+fn format_key_values(record: &Record) -> String {
+    use log::kv::{Error, Key, Value, VisitSource};
+
+    struct Collector {
+        buffer: String,
+    }
+
+    impl<'kvs> VisitSource<'kvs> for Collector {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+            if !self.buffer.is_empty() {
+                self.buffer.push(' ');
+            }
+
+            push_quoted_if_needed(&mut self.buffer, key.as_str());
+            self.buffer.push('=');
+            push_quoted_if_needed(&mut self.buffer, &value.to_string());
+
+            Ok(())
+        }
+    }
+
+    let mut collector = Collector {
+        buffer: String::new(),
+    };
+    let _ = record.key_values().visit(&mut collector);
+    collector.buffer
+}
+
+/// Quotes `value` if it contains a space, `=`, or newline, escaping any
+/// newline as `\n` so it can't break the single-line output.
+#[cfg(feature = "kv")]
+fn push_quoted_if_needed(buffer: &mut String, value: &str) {
+    if value.contains(' ') || value.contains('=') || value.contains('\n') {
+        buffer.push('"');
+        for ch in value.chars() {
+            if ch == '\n' {
+                buffer.push_str("\\n");
+            } else {
+                buffer.push(ch);
+            }
+        }
+        buffer.push('"');
+    } else {
+        buffer.push_str(value);
+    }
+}
+
+#[cfg(not(feature = "kv"))]
+fn format_key_values(_record: &Record) -> String {
+    String::new()
+}
+
+/// Builds one journal entry in the native wire protocol.
+fn journald_entry(record: &Record) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    push_journald_field(
+        &mut buffer,
+        "PRIORITY",
+        &level_to_severity_rfc5424(record.level()).to_string(),
+    );
+    push_journald_field(&mut buffer, "MESSAGE", &record.args().to_string());
+    push_journald_field(&mut buffer, "TARGET", record.target());
+
+    if let Some(file) = record.file() {
+        push_journald_field(&mut buffer, "CODE_FILE", file);
+    }
+    if let Some(line) = record.line() {
+        push_journald_field(&mut buffer, "CODE_LINE", &line.to_string());
+    }
+
+    push_journald_key_values(&mut buffer, record);
+
+    buffer
+}
+
+/// Appends a record's key-values to a journal entry as their own fields,
+/// uppercasing each key to match journald's field name convention.
+#[cfg(feature = "kv")]
+fn push_journald_key_values(buffer: &mut Vec<u8>, record: &Record) {
+    use log::kv::{Error, Key, Value, VisitSource};
+
+    struct Collector<'a> {
+        buffer: &'a mut Vec<u8>,
+    }
+
+    impl<'kvs> VisitSource<'kvs> for Collector<'_> {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+            let field_name = key.as_str().to_ascii_uppercase();
+            push_journald_field(self.buffer, &field_name, &value.to_string());
+            Ok(())
+        }
+    }
+
+    let mut collector = Collector { buffer };
+    let _ = record.key_values().visit(&mut collector);
+}
+
+#[cfg(not(feature = "kv"))]
+fn push_journald_key_values(_buffer: &mut Vec<u8>, _record: &Record) {}
+
+/// Appends one `FIELD=value` line, using the binary form for multi-line values.
+fn push_journald_field(buffer: &mut Vec<u8>, name: &str, value: &str) {
+    if value.contains('\n') {
+        buffer.extend_from_slice(name.as_bytes());
+        buffer.push(b'\n');
+        buffer.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        buffer.extend_from_slice(value.as_bytes());
+        buffer.push(b'\n');
+    } else {
+        buffer.extend_from_slice(name.as_bytes());
+        buffer.push(b'=');
+        buffer.extend_from_slice(value.as_bytes());
+        buffer.push(b'\n');
+    }
+}
+
 // Tests
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
     use log::{Level, LevelFilter, Log, Metadata};
 
-    use crate::{AppLogger, LoggerModuleFilterKey};
+    use crate::{AppLogger, LogSink, LoggerModuleFilterKey, StdioSink};
 
-    fn metadata<'a>(target: &'a str, level: Level) -> Metadata {
+    fn metadata<'a>(target: &'a str, level: Level) -> Metadata<'a> {
         Metadata::builder().target(target).level(level).build()
     }
 
@@ -113,20 +408,24 @@ mod tests {
     fn no_filters() {
         let logger = AppLogger {
             module_max_levels: vec![],
+            journald: None,
+            sink: Box::new(StdioSink),
         };
-        assert_eq!(logger.enabled(&metadata("test1", Level::Error)), false);
+        assert!(!(logger.enabled(&metadata("test1", Level::Error))));
     }
 
     #[test]
     fn no_default_filters() {
         let logger = AppLogger {
             module_max_levels: vec![LoggerModuleFilterKey::Module("test1", LevelFilter::Info)],
+            journald: None,
+            sink: Box::new(StdioSink),
         };
-        assert_eq!(logger.enabled(&metadata("test1", Level::Info)), true);
-        assert_eq!(logger.enabled(&metadata("test1", Level::Trace)), false);
+        assert!(logger.enabled(&metadata("test1", Level::Info)));
+        assert!(!(logger.enabled(&metadata("test1", Level::Trace))));
 
-        assert_eq!(logger.enabled(&metadata("test2", Level::Info)), false);
-        assert_eq!(logger.enabled(&metadata("test2", Level::Trace)), false);
+        assert!(!(logger.enabled(&metadata("test2", Level::Info))));
+        assert!(!(logger.enabled(&metadata("test2", Level::Trace))));
     }
 
     #[test]
@@ -136,14 +435,16 @@ mod tests {
                 LoggerModuleFilterKey::Module("test1", LevelFilter::Info),
                 LoggerModuleFilterKey::Default(LevelFilter::Warn),
             ],
+            journald: None,
+            sink: Box::new(StdioSink),
         };
-        assert_eq!(logger.enabled(&metadata("test1", Level::Warn)), true);
-        assert_eq!(logger.enabled(&metadata("test1", Level::Info)), true);
-        assert_eq!(logger.enabled(&metadata("test1", Level::Trace)), false);
+        assert!(logger.enabled(&metadata("test1", Level::Warn)));
+        assert!(logger.enabled(&metadata("test1", Level::Info)));
+        assert!(!(logger.enabled(&metadata("test1", Level::Trace))));
 
-        assert_eq!(logger.enabled(&metadata("test2", Level::Warn)), true);
-        assert_eq!(logger.enabled(&metadata("test2", Level::Info)), false);
-        assert_eq!(logger.enabled(&metadata("test2", Level::Trace)), false);
+        assert!(logger.enabled(&metadata("test2", Level::Warn)));
+        assert!(!(logger.enabled(&metadata("test2", Level::Info))));
+        assert!(!(logger.enabled(&metadata("test2", Level::Trace))));
     }
 
     #[test]
@@ -155,22 +456,189 @@ mod tests {
                 LoggerModuleFilterKey::Default(LevelFilter::Error),
                 LoggerModuleFilterKey::Default(LevelFilter::Trace),
             ],
+            journald: None,
+            sink: Box::new(StdioSink),
         };
-        assert_eq!(logger.enabled(&metadata("test1", Level::Error)), true);
-        assert_eq!(logger.enabled(&metadata("test1", Level::Trace)), false);
+        assert!(logger.enabled(&metadata("test1", Level::Error)));
+        assert!(!(logger.enabled(&metadata("test1", Level::Trace))));
 
-        assert_eq!(logger.enabled(&metadata("test2", Level::Error)), true);
-        assert_eq!(logger.enabled(&metadata("test2", Level::Trace)), false);
+        assert!(logger.enabled(&metadata("test2", Level::Error)));
+        assert!(!(logger.enabled(&metadata("test2", Level::Trace))));
     }
 
     #[test]
-    fn target_child_module() {
+    fn filter_most_specific_wins() {
         let logger = AppLogger {
-            module_max_levels: vec![LoggerModuleFilterKey::Module("test1", LevelFilter::Error)],
+            module_max_levels: vec![
+                LoggerModuleFilterKey::Module("test1", LevelFilter::Error),
+                LoggerModuleFilterKey::Module("test1::child", LevelFilter::Trace),
+            ],
+            journald: None,
+            sink: Box::new(StdioSink),
         };
+        assert!(!(logger.enabled(&metadata("test1", Level::Trace))));
+        assert!(logger.enabled(&metadata("test1::child", Level::Trace)));
+    }
+
+    #[test]
+    fn filter_does_not_match_partial_module_name() {
+        let logger = AppLogger {
+            module_max_levels: vec![LoggerModuleFilterKey::Module("app", LevelFilter::Error)],
+            journald: None,
+            sink: Box::new(StdioSink),
+        };
+        assert!(logger.enabled(&metadata("app", Level::Error)));
+        assert!(!(logger.enabled(&metadata("application", Level::Error))));
+    }
+
+    #[test]
+    fn parse_directives_default_only() {
+        let directives = crate::parse_directives("info").unwrap();
+        assert_eq!(crate::most_verbose_level(&directives), LevelFilter::Info);
+    }
+
+    #[test]
+    fn parse_directives_module_and_default() {
+        let directives = crate::parse_directives("test1=Debug,warn").unwrap();
+        assert_eq!(directives.len(), 2);
+        match &directives[0] {
+            LoggerModuleFilterKey::Module(name, level) => {
+                assert_eq!(*name, "test1");
+                assert_eq!(*level, LevelFilter::Debug);
+            }
+            LoggerModuleFilterKey::Default(_) => panic!("expected a module filter"),
+        }
+        match &directives[1] {
+            LoggerModuleFilterKey::Default(level) => assert_eq!(*level, LevelFilter::Warn),
+            LoggerModuleFilterKey::Module(..) => panic!("expected a default filter"),
+        }
+    }
+
+    #[test]
+    fn parse_directives_unknown_level_is_error() {
+        assert!(crate::parse_directives("test1=verbose").is_err());
+    }
+
+    #[cfg(feature = "kv")]
+    #[test]
+    fn format_key_values_quotes_unsafe_keys() {
+        let record = log::Record::builder()
+            .args(format_args!("request done"))
+            .target("test1")
+            .level(Level::Info)
+            .key_values(&[("user_id", 42), ("a b", 1)])
+            .build();
+
+        assert_eq!(crate::format_key_values(&record), "user_id=42 \"a b\"=1");
+    }
+
+    #[cfg(feature = "kv")]
+    #[test]
+    fn format_key_values_quotes_unsafe_values() {
+        let record = log::Record::builder()
+            .args(format_args!("msg"))
+            .target("test1")
+            .level(Level::Info)
+            .key_values(&[("name", "john doe")])
+            .build();
+
+        assert_eq!(crate::format_key_values(&record), "name=\"john doe\"");
+    }
+
+    #[cfg(feature = "kv")]
+    #[test]
+    fn format_key_values_escapes_embedded_newlines() {
+        let record = log::Record::builder()
+            .args(format_args!("msg"))
+            .target("test1")
+            .level(Level::Info)
+            .key_values(&[("trace", "line1\nline2")])
+            .build();
+
+        assert_eq!(
+            crate::format_key_values(&record),
+            "trace=\"line1\\nline2\""
+        );
+    }
+
+    #[test]
+    fn push_journald_field_simple_value() {
+        let mut buffer = Vec::new();
+        crate::push_journald_field(&mut buffer, "TARGET", "test1");
+        assert_eq!(buffer, b"TARGET=test1\n");
+    }
+
+    #[test]
+    fn push_journald_field_multiline_value() {
+        let mut buffer = Vec::new();
+        crate::push_journald_field(&mut buffer, "MESSAGE", "line1\nline2");
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"MESSAGE\n");
+        expected.extend_from_slice(&11u64.to_le_bytes());
+        expected.extend_from_slice(b"line1\nline2");
+        expected.push(b'\n');
+
+        assert_eq!(buffer, expected);
+    }
+
+    #[cfg(feature = "kv")]
+    #[test]
+    fn journald_entry_includes_key_values() {
+        let record = log::Record::builder()
+            .args(format_args!("request done"))
+            .target("test1")
+            .level(Level::Info)
+            .key_values(&[("user_id", 42)])
+            .build();
+
+        let entry = String::from_utf8(crate::journald_entry(&record)).unwrap();
+        assert!(entry.contains("USER_ID=42\n"));
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturingSink {
+        lines: Arc<Mutex<Vec<(Level, String)>>>,
+    }
+
+    impl LogSink for CapturingSink {
+        fn write_line(&self, level: Level, line: &str) {
+            self.lines.lock().unwrap().push((level, line.to_string()));
+        }
+    }
+
+    #[test]
+    fn log_writes_formatted_line_to_sink() {
+        let sink = CapturingSink::default();
+        let logger = AppLogger {
+            module_max_levels: vec![LoggerModuleFilterKey::Default(LevelFilter::Info)],
+            journald: None,
+            sink: Box::new(sink.clone()),
+        };
+
+        logger.log(
+            &log::Record::builder()
+                .args(format_args!("Hello, World!"))
+                .target("test1")
+                .level(Level::Info)
+                .build(),
+        );
+
+        let lines = sink.lines.lock().unwrap();
+        assert_eq!(lines.len(), 1);
         assert_eq!(
-            logger.enabled(&metadata("test1::child", Level::Error)),
-            true
+            lines[0],
+            (Level::Info, "<6>test1: Hello, World!".to_string())
         );
     }
+
+    #[test]
+    fn target_child_module() {
+        let logger = AppLogger {
+            module_max_levels: vec![LoggerModuleFilterKey::Module("test1", LevelFilter::Error)],
+            journald: None,
+            sink: Box::new(StdioSink),
+        };
+        assert!(logger.enabled(&metadata("test1::child", Level::Error)));
+    }
 }